@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::solvepy::Mode;
+
+/// Command-line options for pwninit
+#[derive(Debug, Parser)]
+#[command(author, version, about)]
+pub struct Opts {
+    /// Path to challenge binary
+    #[arg(long)]
+    pub bin: Option<PathBuf>,
+
+    /// Path to libc binary
+    #[arg(long)]
+    pub libc: Option<PathBuf>,
+
+    /// Path to dynamic linker
+    #[arg(long)]
+    pub ld: Option<PathBuf>,
+
+    /// Path to a custom solve script template, overriding the built-in one
+    #[arg(long)]
+    pub template_path: Option<PathBuf>,
+
+    /// Variable name bound to the binary's `ELF` in the template
+    #[arg(long)]
+    pub template_bin_name: Option<String>,
+
+    /// Variable name bound to the libc's `ELF` in the template
+    #[arg(long)]
+    pub template_libc_name: Option<String>,
+
+    /// Variable name bound to the linker's `ELF` in the template
+    #[arg(long)]
+    pub template_ld_name: Option<String>,
+
+    /// Which half of the generated `start()` dispatcher to emit [default:
+    /// local, unless --host or --port is given, in which case: both]
+    #[arg(long, value_enum)]
+    pub mode: Option<Mode>,
+
+    /// Remote host to connect to in `--mode remote`/`--mode both`
+    #[arg(long)]
+    pub host: Option<String>,
+
+    /// Remote port to connect to in `--mode remote`/`--mode both`
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// Path to a gdbscript to run via the `{{gdb_attach}}` template helper
+    #[arg(long)]
+    pub gdb_script: Option<PathBuf>,
+}
@@ -5,10 +5,19 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::string;
 
+use clap::ValueEnum;
 use colored::Colorize;
 use ex::fs;
 use ex::io;
+use handlebars::handlebars_helper;
+use handlebars::Context;
 use handlebars::Handlebars;
+use handlebars::Helper;
+use handlebars::HelperDef;
+use handlebars::HelperResult;
+use handlebars::Output;
+use handlebars::RenderContext;
+use serde::Deserialize;
 use serde::Serialize;
 use snafu::ResultExt;
 use snafu::Snafu;
@@ -33,19 +42,201 @@ pub enum Error {
 
     #[snafu(display("error setting solve script template executable: {}", source))]
     SetExecError { source: io::Error },
+
+    #[snafu(display("error reading pwninit.toml: {}", source))]
+    ConfigReadError { source: io::Error },
+
+    #[snafu(display("pwninit.toml is not valid UTF-8: {}", source))]
+    ConfigUtf8Error { source: string::FromUtf8Error },
+
+    #[snafu(display("error parsing pwninit.toml: {}", source))]
+    ConfigParseError { source: toml::de::Error },
+
+    #[snafu(display(
+        "--host and --port are required for `--mode {}` (pass both, or use `--mode local`)",
+        mode
+    ))]
+    MissingRemoteInfoError { mode: &'static str },
+
+    #[snafu(display("error reading gdbscript: {}", source))]
+    GdbScriptReadError { source: io::Error },
+
+    #[snafu(display("gdbscript is not valid UTF-8: {}", source))]
+    GdbScriptUtf8Error { source: string::FromUtf8Error },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Which half of a pwntools `start()` dispatcher to emit: a local
+/// `process()`, a `remote()` connection, or both (switched on
+/// `args.REMOTE` at runtime). See [`resolve_mode`] for how the *implicit*
+/// default (no `--mode` given) differs from this enum's own `Both`
+/// default below.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Local,
+    Remote,
+    Both,
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Mode::Both
+    }
+}
+
+impl Mode {
+    /// The lowercase spelling accepted by `--mode`, used in error messages
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Local => "local",
+            Mode::Remote => "remote",
+            Mode::Both => "both",
+        }
+    }
+}
+
+const DEFAULT_TEMPLATE_BIN_NAME: &str = "exe";
+const DEFAULT_TEMPLATE_LIBC_NAME: &str = "libc";
+const DEFAULT_TEMPLATE_LD_NAME: &str = "ld";
+
+/// `pwninit.toml` fields that override the built-in template defaults,
+/// searched for in the project dir and then `$XDG_CONFIG_HOME/pwninit/`
+#[derive(Debug, Default, Deserialize)]
+struct Config {
+    template_path: Option<PathBuf>,
+    template_bin_name: Option<String>,
+    template_libc_name: Option<String>,
+    template_ld_name: Option<String>,
+    mode: Option<Mode>,
+}
+
+/// Resolved template settings: CLI flags override `pwninit.toml`, which
+/// overrides the built-in defaults
+struct ResolvedOpts {
+    template_path: Option<PathBuf>,
+    bin_name: String,
+    libc_name: String,
+    ld_name: String,
+    mode: Mode,
+}
+
+/// Load `pwninit.toml` from the project dir, falling back to
+/// `$XDG_CONFIG_HOME/pwninit/pwninit.toml` (or `~/.config/pwninit/` if
+/// `XDG_CONFIG_HOME` isn't set). Returns the default (empty) config if
+/// neither exists.
+fn load_config() -> Result<Config> {
+    let xdg_config_path = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("pwninit").join("pwninit.toml"));
+
+    let candidates = [Some(PathBuf::from("pwninit.toml")), xdg_config_path]
+        .into_iter()
+        .flatten();
+
+    for path in candidates {
+        if path.exists() {
+            let data = fs::read(&path).context(ConfigReadError)?;
+            let data = String::from_utf8(data).context(ConfigUtf8Error)?;
+            return toml::from_str(&data).context(ConfigParseError);
+        }
+    }
+
+    Ok(Config::default())
+}
+
+/// Merge a CLI flag over a `pwninit.toml` value over a built-in default:
+/// the flag wins if the user set it, then the config file, then the
+/// default. Both `cli` and `config` must be `None` when unset (as
+/// opposed to a default-value sentinel) so an explicit flag that happens
+/// to match the default isn't mistaken for "not set".
+fn merge_setting<T>(cli: Option<T>, config: Option<T>, default: T) -> T {
+    cli.or(config).unwrap_or(default)
+}
+
+/// Resolve the effective `Mode`, falling back to `Local` only when no
+/// `--host`/`--port` was passed either, so a bare invocation doesn't
+/// demand remote info nobody asked for
+fn resolve_mode(cli: Option<Mode>, config: Option<Mode>, have_host: bool, have_port: bool) -> Mode {
+    match cli.or(config) {
+        Some(mode) => mode,
+        None if have_host || have_port => Mode::default(),
+        None => Mode::Local,
+    }
+}
+
+/// Reject a `mode` that needs `HOST`/`PORT` to render correctly when
+/// `--host`/`--port` weren't both supplied. See [`resolve_mode`] for why
+/// this can fire even for the implicit default mode.
+fn check_remote_info(mode: Mode, have_host: bool, have_port: bool) -> Result<()> {
+    if matches!(mode, Mode::Remote | Mode::Both) && (!have_host || !have_port) {
+        return Err(Error::MissingRemoteInfoError { mode: mode.name() });
+    }
+    Ok(())
+}
+
+/// Merge CLI flags over `config`, falling back to the built-in defaults
+///
+/// Requires every `opts.template_*`/`opts.mode` field to be `Option`-typed
+/// (`crate::opts::Opts`, defined outside this module), so [`merge_setting`]
+/// can tell "unset" apart from "set to the default value".
+fn merge_opts(opts: &Opts, config: &Config) -> ResolvedOpts {
+    let template_path = opts
+        .template_path
+        .clone()
+        .or_else(|| config.template_path.clone());
+
+    let bin_name = merge_setting(
+        opts.template_bin_name.clone(),
+        config.template_bin_name.clone(),
+        DEFAULT_TEMPLATE_BIN_NAME.to_string(),
+    );
+
+    let libc_name = merge_setting(
+        opts.template_libc_name.clone(),
+        config.template_libc_name.clone(),
+        DEFAULT_TEMPLATE_LIBC_NAME.to_string(),
+    );
+
+    let ld_name = merge_setting(
+        opts.template_ld_name.clone(),
+        config.template_ld_name.clone(),
+        DEFAULT_TEMPLATE_LD_NAME.to_string(),
+    );
+
+    let mode = resolve_mode(
+        opts.mode,
+        config.mode,
+        opts.host.is_some(),
+        opts.port.is_some(),
+    );
+
+    ResolvedOpts {
+        template_path,
+        bin_name,
+        libc_name,
+        ld_name,
+        mode,
+    }
+}
+
 #[derive(Serialize)]
 struct Bindings {
     exe: String,
     libc: String,
+    ld: String,
+    bindings: String,
+    proc_args: String,
+    host: String,
+    port: String,
+    start: String,
 }
 
 /// Make pwntools script that binds the (binary, libc, linker) to `ELF`
 /// variables
-fn _make_bindings(opts: &Opts) -> String {
+fn _make_bindings(opts: &Opts, resolved: &ResolvedOpts) -> String {
     // Helper to make one binding line
     let bind_line = |name: &str, opt_path: &Option<PathBuf>| -> Option<String> {
         opt_path
@@ -55,9 +246,9 @@ fn _make_bindings(opts: &Opts) -> String {
 
     // Create bindings and join them with newlines
     [
-        bind_line(&opts.template_bin_name, &opts.bin),
-        bind_line(&opts.template_libc_name, &opts.libc),
-        bind_line(&opts.template_ld_name, &opts.ld),
+        bind_line(&resolved.bin_name, &opts.bin),
+        bind_line(&resolved.libc_name, &opts.libc),
+        bind_line(&resolved.ld_name, &opts.ld),
     ]
     .iter()
     .filter_map(|x| x.as_ref())
@@ -67,18 +258,15 @@ fn _make_bindings(opts: &Opts) -> String {
 }
 
 /// Make arguments to pwntools `process()` function
-fn _make_proc_args(opts: &Opts) -> String {
+fn _make_proc_args(opts: &Opts, resolved: &ResolvedOpts) -> String {
     let args = if opts.ld.is_some() {
-        format!(
-            "{}.path, {}.path",
-            opts.template_ld_name, opts.template_bin_name
-        )
+        format!("{}.path, {}.path", resolved.ld_name, resolved.bin_name)
     } else {
-        format!("{}.path", opts.template_bin_name)
+        format!("{}.path", resolved.bin_name)
     };
 
     let env = if opts.libc.is_some() {
-        format!(", env={{\"LD_PRELOAD\": {}.path}}", opts.template_libc_name)
+        format!(", env={{\"LD_PRELOAD\": {}.path}}", resolved.libc_name)
     } else {
         "".to_string()
     };
@@ -86,9 +274,103 @@ fn _make_proc_args(opts: &Opts) -> String {
     format!("[{}]{}", args, env)
 }
 
+/// Make a pwntools `start()` helper for `mode`: `Local` runs the local
+/// binary (via `proc_args`, from `_make_proc_args`), `Remote` connects to
+/// `host`/`port` (inlined as literal `remote(...)` arguments, from
+/// `--host`/`--port`), and `Both` dispatches between the two on
+/// `args.REMOTE`
+fn _make_start(proc_args: &str, mode: Mode, host: &str, port: &str) -> String {
+    let host = host.replace('\\', "\\\\").replace('"', "\\\"");
+
+    let local_branch = format!("return process({})", proc_args);
+    let remote_branch = format!("return remote(\"{}\", {})", host, port);
+
+    let body = match mode {
+        Mode::Local => format!("    {}", local_branch),
+        Mode::Remote => format!("    {}", remote_branch),
+        Mode::Both => format!(
+            "    if args.REMOTE:\n        {}\n    else:\n        {}",
+            remote_branch, local_branch
+        ),
+    };
+
+    format!("def start():\n{}\n", body)
+}
+
+handlebars_helper!(elf_helper: |name: str, path: str| format!("{} = ELF(\"{}\")", name, path));
+
+/// Build the `{{#if_libc}}...{{else}}...{{/if_libc}}` block helper,
+/// selecting the template branch when `has_libc` and the `{{else}}`
+/// branch (or nothing, if absent) otherwise
+fn if_libc_helper(has_libc: bool) -> Box<dyn HelperDef + Send + Sync> {
+    Box::new(
+        move |h: &Helper,
+              r: &Handlebars,
+              ctx: &Context,
+              rc: &mut RenderContext,
+              out: &mut dyn Output|
+              -> HelperResult {
+            let tmpl = if has_libc { h.template() } else { h.inverse() };
+            match tmpl {
+                Some(t) => t.render(r, ctx, rc, out),
+                None => Ok(()),
+            }
+        },
+    )
+}
+
+/// Build the `{{gdb_attach}}` helper, expanding to a `gdb.attach(...)`
+/// stanza that runs `gdbscript` (the *contents* of `--gdb-script`, read
+/// up front since `gdb.attach`'s `gdbscript` argument is GDB commands,
+/// not a path) when set, and emitting nothing otherwise
+fn gdb_attach_helper(gdbscript: Option<String>) -> Box<dyn HelperDef + Send + Sync> {
+    Box::new(
+        move |_: &Helper,
+              _: &Handlebars,
+              _: &Context,
+              _: &mut RenderContext,
+              out: &mut dyn Output|
+              -> HelperResult {
+            if let Some(gdbscript) = &gdbscript {
+                out.write(&format!(
+                    "gdb.attach(io, gdbscript=\"\"\"\n{}\n\"\"\")\n",
+                    gdbscript
+                ))?;
+            }
+            Ok(())
+        },
+    )
+}
+
+/// Register pwninit's Handlebars helpers so templates can compose output
+/// instead of relying on pre-baked strings: `{{elf name path}}`,
+/// `{{#if_libc}}...{{/if_libc}}`, and `{{gdb_attach}}`
+fn register_helpers(handlebars: &mut Handlebars, opts: &Opts) -> Result<()> {
+    // These are Python templates, not HTML: don't let Handlebars'
+    // default escape function mangle `"` in helper output
+    handlebars.register_escape_fn(handlebars::no_escape);
+
+    let gdbscript = match &opts.gdb_script {
+        Some(path) => {
+            let data = fs::read(path).context(GdbScriptReadError)?;
+            Some(String::from_utf8(data).context(GdbScriptUtf8Error)?)
+        }
+        None => None,
+    };
+
+    handlebars.register_helper("elf", Box::new(elf_helper));
+    handlebars.register_helper("if_libc", if_libc_helper(opts.libc.is_some()));
+    handlebars.register_helper("gdb_attach", gdb_attach_helper(gdbscript));
+
+    Ok(())
+}
+
 /// Fill in template pwntools solve script with (binary, libc, linker) paths
 fn make_stub(opts: &Opts) -> Result<String> {
-    let templ = match &opts.template_path {
+    let config = load_config()?;
+    let resolved = merge_opts(opts, &config);
+
+    let templ = match &resolved.template_path {
         Some(path) => {
             let data = fs::read(path).context(ReadError)?;
             String::from_utf8(data).context(Utf8Error)?
@@ -106,10 +388,33 @@ fn make_stub(opts: &Opts) -> Result<String> {
         None => "".to_string(),
     };
 
+    let ld = match opts.ld.as_ref() {
+        Some(l) => l.to_str().unwrap().to_string(),
+        None => "".to_string(),
+    };
+
+    check_remote_info(resolved.mode, opts.host.is_some(), opts.port.is_some())?;
+
+    let bindings = _make_bindings(opts, &resolved);
+    let proc_args = _make_proc_args(opts, &resolved);
+    let host = opts.host.clone().unwrap_or_default();
+    let port = opts.port.map(|p| p.to_string()).unwrap_or_default();
+    let start = _make_start(&proc_args, resolved.mode, &host, &port);
+
     let mut handlebars = Handlebars::new();
+    register_helpers(&mut handlebars, opts)?;
     handlebars.register_template_string("solve", templ.to_owned()).context(TmplError)?;
 
-    let mapping = Bindings { exe, libc };
+    let mapping = Bindings {
+        exe,
+        libc,
+        ld,
+        bindings,
+        proc_args,
+        host,
+        port,
+        start,
+    };
 
     Ok(handlebars.render("solve", &mapping).context(RenderError)?)
 }
@@ -126,3 +431,194 @@ pub fn write_stub(opts: &Opts) -> Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_setting_prefers_cli_then_config_then_default() {
+        assert_eq!(
+            merge_setting(Some("a".to_string()), Some("b".to_string()), "c".to_string()),
+            "a"
+        );
+        assert_eq!(
+            merge_setting(None, Some("b".to_string()), "c".to_string()),
+            "b"
+        );
+        assert_eq!(merge_setting(None, None, "c".to_string()), "c");
+
+        // A CLI value that happens to match the default must still win
+        // over a config file value.
+        assert_eq!(
+            merge_setting(Some(Mode::Both), Some(Mode::Local), Mode::Both),
+            Mode::Both
+        );
+    }
+
+    #[test]
+    fn resolve_mode_falls_back_to_local_for_a_bare_invocation() {
+        // No --mode flag, no pwninit.toml mode, and no --host/--port: the
+        // default, flag-less `pwninit` invocation must still resolve to
+        // `Local`, not `Mode::default()` (`Both`), or it'll demand remote
+        // info nobody asked for.
+        assert_eq!(resolve_mode(None, None, false, false), Mode::Local);
+
+        // ...but falls back to the real default as soon as either flag is
+        // present, even just one: that's enough signal that the user
+        // wants a remote-capable script, and `check_remote_info` is what
+        // turns "only one of the two" into a real error rather than a
+        // silently dropped flag.
+        assert_eq!(resolve_mode(None, None, true, false), Mode::default());
+        assert_eq!(resolve_mode(None, None, false, true), Mode::default());
+        assert_eq!(resolve_mode(None, None, true, true), Mode::default());
+    }
+
+    #[test]
+    fn resolve_mode_plus_check_remote_info_rejects_one_flag_without_the_other() {
+        // A lone --host (or --port), with no explicit --mode, must not
+        // silently fall back to a plain local script: resolve_mode hands
+        // back the real default so check_remote_info can reject it.
+        let mode = resolve_mode(None, None, true, false);
+        assert!(check_remote_info(mode, true, false).is_err());
+
+        let mode = resolve_mode(None, None, false, true);
+        assert!(check_remote_info(mode, false, true).is_err());
+    }
+
+    #[test]
+    fn resolve_mode_prefers_explicit_cli_then_config_over_the_implicit_default() {
+        assert_eq!(
+            resolve_mode(Some(Mode::Remote), Some(Mode::Local), false, false),
+            Mode::Remote
+        );
+        assert_eq!(
+            resolve_mode(None, Some(Mode::Both), false, false),
+            Mode::Both
+        );
+    }
+
+    #[test]
+    fn check_remote_info_errors_for_remote_modes_without_host_and_port() {
+        // Local never needs remote info, so it never errors.
+        assert!(check_remote_info(Mode::Local, false, false).is_ok());
+
+        // Remote/Both require both --host and --port, whether the mode was
+        // requested explicitly or resolved implicitly by resolve_mode.
+        assert!(check_remote_info(Mode::Remote, false, false).is_err());
+        assert!(check_remote_info(Mode::Remote, true, false).is_err());
+        assert!(check_remote_info(Mode::Both, false, true).is_err());
+        assert!(check_remote_info(Mode::Remote, true, true).is_ok());
+        assert!(check_remote_info(Mode::Both, true, true).is_ok());
+    }
+
+    #[test]
+    fn bindings_context_exposes_ld_bindings_and_proc_args() {
+        let mapping = Bindings {
+            exe: "exe".to_string(),
+            libc: "libc".to_string(),
+            ld: "ld".to_string(),
+            bindings: "exe = ELF(\"chall\")\nld = ELF(\"ld.so\")".to_string(),
+            proc_args: "[ld.path, exe.path], env={\"LD_PRELOAD\": libc.path}".to_string(),
+            host: "".to_string(),
+            port: "".to_string(),
+            start: "".to_string(),
+        };
+
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(handlebars::no_escape);
+        handlebars
+            .register_template_string("t", "{{{bindings}}}\nprocess({{{proc_args}}})\n{{ld}}")
+            .unwrap();
+
+        let rendered = handlebars.render("t", &mapping).unwrap();
+        assert_eq!(
+            rendered,
+            "exe = ELF(\"chall\")\nld = ELF(\"ld.so\")\n\
+             process([ld.path, exe.path], env={\"LD_PRELOAD\": libc.path})\n\
+             ld"
+        );
+    }
+
+    #[test]
+    fn elf_helper_output_is_not_html_escaped() {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(handlebars::no_escape);
+        handlebars.register_helper("elf", Box::new(elf_helper));
+        handlebars
+            .register_template_string("t", "{{elf \"exe\" \"chall\"}}")
+            .unwrap();
+
+        let rendered = handlebars.render("t", &()).unwrap();
+        assert_eq!(rendered, "exe = ELF(\"chall\")");
+    }
+
+    #[test]
+    fn make_start_renders_exact_body_per_mode() {
+        assert_eq!(
+            _make_start("[exe.path]", Mode::Local, "10.0.0.1", "1337"),
+            "def start():\n    return process([exe.path])\n"
+        );
+
+        assert_eq!(
+            _make_start("[exe.path]", Mode::Remote, "10.0.0.1", "1337"),
+            "def start():\n    return remote(\"10.0.0.1\", 1337)\n"
+        );
+
+        assert_eq!(
+            _make_start("[exe.path]", Mode::Both, "10.0.0.1", "1337"),
+            "def start():\n    if args.REMOTE:\n        return remote(\"10.0.0.1\", 1337)\n    else:\n        return process([exe.path])\n"
+        );
+    }
+
+    #[test]
+    fn make_start_escapes_quotes_and_backslashes_in_host() {
+        assert_eq!(
+            _make_start("[exe.path]", Mode::Remote, "host\"); os.system(\"evil", "1337"),
+            "def start():\n    return remote(\"host\\\"); os.system(\\\"evil\", 1337)\n"
+        );
+
+        assert_eq!(
+            _make_start("[exe.path]", Mode::Remote, "a\\\"b", "1337"),
+            "def start():\n    return remote(\"a\\\\\\\"b\", 1337)\n"
+        );
+    }
+
+    #[test]
+    fn if_libc_helper_selects_template_or_inverse_branch() {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper("if_libc", if_libc_helper(true));
+        handlebars
+            .register_template_string("t", "{{#if_libc}}a{{else}}b{{/if_libc}}")
+            .unwrap();
+        assert_eq!(handlebars.render("t", &()).unwrap(), "a");
+
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper("if_libc", if_libc_helper(false));
+        handlebars
+            .register_template_string("t", "{{#if_libc}}a{{else}}b{{/if_libc}}")
+            .unwrap();
+        assert_eq!(handlebars.render("t", &()).unwrap(), "b");
+    }
+
+    #[test]
+    fn gdb_attach_helper_emits_only_when_gdb_script_is_set() {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(handlebars::no_escape);
+        handlebars.register_helper("gdb_attach", gdb_attach_helper(None));
+        handlebars.register_template_string("t", "{{gdb_attach}}").unwrap();
+        assert_eq!(handlebars.render("t", &()).unwrap(), "");
+
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(handlebars::no_escape);
+        handlebars.register_helper(
+            "gdb_attach",
+            gdb_attach_helper(Some("break main\ncontinue".to_string())),
+        );
+        handlebars.register_template_string("t", "{{gdb_attach}}").unwrap();
+        assert_eq!(
+            handlebars.render("t", &()).unwrap(),
+            "gdb.attach(io, gdbscript=\"\"\"\nbreak main\ncontinue\n\"\"\")\n"
+        );
+    }
+}